@@ -1,16 +1,30 @@
+use ab_glyph::{FontArc, PxScale};
 use clap::{Parser, ValueEnum};
+use imageproc::drawing::{draw_hollow_rect_mut, draw_text_mut};
+use imageproc::rect::Rect;
 use log::{error, info};
 use rust_paddle_ocr::{OcrEngineManager, OcrError, OcrResult};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::io::{self, Write};
 
+// 交互模式与批处理模式共用的受支持图片扩展名
+const SUPPORTED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "bmp", "tiff", "webp"];
+
+// 批处理缓存落盘的检查点间隔：每识别出这么多个新结果才重写一次缓存文件，
+// 避免对一个有N张新图的目录整体重写O(N)次缓存（即O(n^2)字节写入），
+// 代价是崩溃时最多丢失这个间隔内尚未落盘的新结果
+const BATCH_CACHE_CHECKPOINT_INTERVAL: usize = 20;
+
 // 根据feature flag选择不同版本的模型
 #[cfg(feature = "v5")]
 mod models {
     pub static DET_MODEL: &[u8] = include_bytes!("../models/PP-OCRv5_mobile_det_fp16.mnn");
     pub static REC_MODEL: &[u8] = include_bytes!("../models/PP-OCRv5_mobile_rec_fp16.mnn");
     pub static KEYS_DATA: &[u8] = include_bytes!("../models/ppocr_keys_v5.txt");
+    pub static CLS_MODEL: &[u8] = include_bytes!("../models/ch_ppocr_mobile_v2.0_cls_infer.mnn");
     pub const VERSION: &str = "v5";
 }
 
@@ -19,18 +33,39 @@ mod models {
     pub static DET_MODEL: &[u8] = include_bytes!("../models/ch_PP-OCRv4_det_infer.mnn");
     pub static REC_MODEL: &[u8] = include_bytes!("../models/ch_PP-OCRv4_rec_infer.mnn");
     pub static KEYS_DATA: &[u8] = include_bytes!("../models/ppocr_keys_v4.txt");
+    pub static CLS_MODEL: &[u8] = include_bytes!("../models/ch_ppocr_mobile_v2.0_cls_infer.mnn");
     pub const VERSION: &str = "v4";
 }
 
-use models::{DET_MODEL, KEYS_DATA, REC_MODEL};
+use models::{CLS_MODEL, DET_MODEL, KEYS_DATA, REC_MODEL};
 
 // 定义输出模式
-#[derive(ValueEnum, Clone, Debug)]
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
 enum OutputMode {
     /// 详细输出模式，使用JSON包含文本内容和位置信息
     Json,
     /// 简单输出模式，仅输出识别的文本内容
     Text,
+    /// 可视化模式，将检测框与识别文本绘制到图像上并保存
+    Visualize,
+}
+
+// 服务模式下使用的协议
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum Protocol {
+    /// 面向人的交互式提示
+    Human,
+    /// 每行一个JSON请求/响应，供程序化调用
+    Json,
+}
+
+// JSON输出中文本框的几何表示方式
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum BoxFormat {
+    /// 轴对齐外接矩形（left/top/width/height）
+    Rect,
+    /// 检测器输出的四角多边形，保留倾斜/旋转文本的真实朝向
+    Quad,
 }
 
 // 命令行参数
@@ -56,22 +91,115 @@ struct Args {
     /// 启动交互模式
     #[arg(short = 's', long = "server")]
     interactive: bool,
+
+    /// 交互模式下使用的协议：human(默认) 或 json(逐行JSON请求/响应)
+    #[arg(long, value_enum, default_value_t = Protocol::Human)]
+    protocol: Protocol,
+
+    /// 启用文字方向分类，自动纠正180度旋转的文本行
+    #[arg(long = "cls")]
+    use_angle_cls: bool,
+
+    /// 判定为180度旋转所需的分类置信度阈值
+    #[arg(long = "cls-threshold", default_value_t = 0.9)]
+    cls_threshold: f32,
+
+    /// 可视化模式下标注图像的保存路径
+    #[arg(long, value_name = "OUTPUT_PATH")]
+    output: Option<PathBuf>,
+
+    /// 可视化模式下绘制文本所用的TrueType字体文件路径（渲染中文等非ASCII文本需要）
+    #[arg(long, value_name = "FONT_PATH")]
+    font: Option<PathBuf>,
+
+    /// DB检测二值化阈值，调低可找回较淡的文字
+    #[arg(long = "det-db-thresh", default_value_t = 0.3)]
+    det_db_thresh: f32,
+
+    /// DB检测候选框过滤阈值，调低可保留更多低置信度的框
+    #[arg(long = "det-box-thresh", default_value_t = 0.6)]
+    det_box_thresh: f32,
+
+    /// 检测框膨胀比例，调高可让裁剪框更宽松
+    #[arg(long = "det-unclip-ratio", default_value_t = 1.5)]
+    det_unclip_ratio: f32,
+
+    /// 对检测分割结果启用膨胀处理
+    #[arg(long = "det-use-dilation")]
+    det_use_dilation: bool,
+
+    /// 检测前图像长边的最大缩放尺寸
+    #[arg(long = "det-max-side-len", default_value_t = 960)]
+    det_max_side_len: u32,
+
+    /// 批处理模式：要处理的图像目录，替代 --path 处理单张图片
+    #[arg(long, value_name = "DIR")]
+    dir: Option<PathBuf>,
+
+    /// 批处理模式下用于筛选文件名的glob模式，例如 "*.png"
+    #[arg(long, value_name = "GLOB")]
+    glob: Option<String>,
+
+    /// 批处理结果缓存所在目录，默认使用 --dir 本身
+    #[arg(long, value_name = "CACHE_DIR")]
+    cache: Option<PathBuf>,
+
+    /// 忽略缓存，强制重新识别所有文件
+    #[arg(long)]
+    force: bool,
+
+    /// JSON输出中文本框的几何表示：rect(外接矩形，默认) 或 quad(四角多边形)
+    #[arg(long = "box-format", value_enum, default_value_t = BoxFormat::Rect)]
+    box_format: BoxFormat,
+}
+
+// DB检测后处理的可调参数，默认值沿用PaddleOCR的标准配置
+#[derive(Debug, Clone)]
+struct DetectionConfig {
+    /// 二值化阈值，调低可找回较淡的文字
+    db_thresh: f32,
+    /// 候选框过滤阈值，调低可保留更多低置信度的框
+    box_thresh: f32,
+    /// 文本框膨胀比例，调高可让裁剪框更宽松
+    unclip_ratio: f32,
+    /// 是否对分割结果做膨胀处理
+    use_dilation: bool,
+    /// 检测前图像长边的最大缩放尺寸
+    max_side_len: u32,
+}
+
+impl From<&Args> for DetectionConfig {
+    fn from(args: &Args) -> Self {
+        Self {
+            db_thresh: args.det_db_thresh,
+            box_thresh: args.det_box_thresh,
+            unclip_ratio: args.det_unclip_ratio,
+            use_dilation: args.det_use_dilation,
+            max_side_len: args.det_max_side_len,
+        }
+    }
 }
 
 // 文本识别结果的JSON表示
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct TextBox {
     text: String,
     confidence: f32,
     position: TextBoxPosition,
 }
 
-#[derive(Serialize, Deserialize)]
-struct TextBoxPosition {
-    left: i32,
-    top: i32,
-    width: u32,
-    height: u32,
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum TextBoxPosition {
+    /// 轴对齐外接矩形
+    Rect {
+        left: i32,
+        top: i32,
+        width: u32,
+        height: u32,
+    },
+    /// 检测器输出的四角多边形 [[x1,y1],[x2,y2],[x3,y3],[x4,y4]]
+    Quad(Vec<[f32; 2]>),
 }
 
 fn main() -> OcrResult<()> {
@@ -96,7 +224,28 @@ fn main() -> OcrResult<()> {
 
     // 检查是否使用交互模式
     if args.interactive {
-        run_interactive_mode(args.mode, args.verbose)?;
+        match args.protocol {
+            Protocol::Human => run_interactive_mode(
+                args.mode,
+                args.verbose,
+                args.use_angle_cls,
+                args.cls_threshold,
+                args.output.as_ref(),
+                args.font.as_ref(),
+                DetectionConfig::from(&args),
+                args.box_format.clone(),
+            )?,
+            Protocol::Json => run_json_protocol_mode(
+                args.verbose,
+                args.use_angle_cls,
+                args.cls_threshold,
+                DetectionConfig::from(&args),
+                args.box_format.clone(),
+            )?,
+        }
+    } else if let Some(dir) = args.dir.clone() {
+        run_batch_mode(&args, &dir)?;
+        info!("Batch OCR process completed");
     } else {
         // 检查是否提供了图片路径
         let image_path = match &args.path {
@@ -118,26 +267,204 @@ fn main() -> OcrResult<()> {
         }
 
         process_ocr(&args, image_path)?;
-        
+
         info!("OCR process completed");
     }
     Ok(())
 }
 
-fn run_interactive_mode(mode: OutputMode, verbose: bool) -> OcrResult<()> {
-    println!("PaddleOCR Interactive Mode Started");
-    println!("Enter image file paths to process (type 'exit' or 'quit' to exit):");
+// JSON协议的请求/响应码：保持与其他OCR子进程协议一致的"code + data"形式
+const PROTOCOL_CODE_SUCCESS: i32 = 100;
+const PROTOCOL_CODE_FILE_NOT_FOUND: i32 = 101;
+const PROTOCOL_CODE_DECODE_FAILURE: i32 = 102;
+const PROTOCOL_CODE_NO_TEXT: i32 = 103;
+const PROTOCOL_CODE_INVALID_REQUEST: i32 = 104;
+const PROTOCOL_CODE_ENGINE_ERROR: i32 = 105;
+// 检测到了文本框，但识别在所有检测框上都失败了——与"画面里确实没有文字"(103)区分开，
+// 便于调用方决定是重试还是放弃
+const PROTOCOL_CODE_RECOGNITION_FAILED: i32 = 106;
+
+#[derive(Deserialize)]
+struct JsonProtocolRequest {
+    image_path: PathBuf,
+}
+
+// `data`复用与--mode json相同的TextBox形状（text/confidence/position），
+// 字段名故意不是text/box/score——这样JSON输出模式和逐行协议模式的响应
+// 结构保持一致，调用方学一套schema即可覆盖两种用法
+#[derive(Serialize)]
+struct JsonProtocolResponse {
+    code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<TextBox>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl JsonProtocolResponse {
+    fn ok(data: Vec<TextBox>) -> Self {
+        Self {
+            code: PROTOCOL_CODE_SUCCESS,
+            data: Some(data),
+            message: None,
+        }
+    }
 
-    // 初始化OCR引擎
+    fn err(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            data: None,
+            message: Some(message.into()),
+        }
+    }
+}
+
+// 初始化OCR引擎，可选启用文字方向分类模型来纠正180度旋转的文本行
+fn init_engine(
+    use_angle_cls: bool,
+    cls_threshold: f32,
+    det_config: DetectionConfig,
+) -> OcrResult<()> {
     info!(
         "Initializing OCR engine from embedded PP-OCR{} models...",
         models::VERSION
     );
+    let cls_model = if use_angle_cls { Some(CLS_MODEL) } else { None };
     OcrEngineManager::initialize_with_config_and_bytes(
         DET_MODEL, REC_MODEL, KEYS_DATA, 12,    // rect_border_size
         false, // merge_boxes
         1,     // merge_threshold
-    )?;
+        cls_model,
+        cls_threshold,
+        det_config,
+    )
+}
+
+// 逐行JSON协议模式：每行读取一个请求对象，写出一个响应对象，
+// 引擎在多次请求之间保持初始化状态，避免每张图片都重新加载模型
+fn run_json_protocol_mode(
+    verbose: bool,
+    use_angle_cls: bool,
+    cls_threshold: f32,
+    det_config: DetectionConfig,
+    box_format: BoxFormat,
+) -> OcrResult<()> {
+    init_engine(use_angle_cls, cls_threshold, det_config)?;
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    for line in stdin.lines() {
+        // Stdin::lines()在遇到非法UTF-8等I/O错误时返回Err；进程必须保持存活，
+        // 否则调用方为避免重复加载模型而保持常驻的前提就被打破了
+        let response = match line {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                match serde_json::from_str::<JsonProtocolRequest>(line) {
+                    Ok(request) => handle_json_protocol_request(&request, verbose, &box_format),
+                    Err(e) => JsonProtocolResponse::err(
+                        PROTOCOL_CODE_INVALID_REQUEST,
+                        format!("Invalid request: {}", e),
+                    ),
+                }
+            }
+            Err(e) => JsonProtocolResponse::err(
+                PROTOCOL_CODE_INVALID_REQUEST,
+                format!("Failed to read request line: {}", e),
+            ),
+        };
+
+        let json = serde_json::to_string(&response)
+            .unwrap_or_else(|_| "{\"code\":105,\"message\":\"failed to serialize response\"}".to_string());
+        writeln!(out, "{}", json).ok();
+        out.flush().ok();
+    }
+
+    Ok(())
+}
+
+fn handle_json_protocol_request(
+    request: &JsonProtocolRequest,
+    verbose: bool,
+    box_format: &BoxFormat,
+) -> JsonProtocolResponse {
+    if !request.image_path.exists() {
+        return JsonProtocolResponse::err(
+            PROTOCOL_CODE_FILE_NOT_FOUND,
+            format!("Input file not found: {:?}", request.image_path),
+        );
+    }
+
+    info!("Loading image from {:?}...", request.image_path);
+    let img = match image::open(&request.image_path) {
+        Ok(img) => img,
+        Err(e) => {
+            return JsonProtocolResponse::err(PROTOCOL_CODE_DECODE_FAILURE, e.to_string());
+        }
+    };
+
+    match recognize_text_boxes(&img, box_format) {
+        Ok((text_boxes, failed_regions)) if text_boxes.is_empty() && failed_regions > 0 => {
+            JsonProtocolResponse::err(
+                PROTOCOL_CODE_RECOGNITION_FAILED,
+                format!(
+                    "Detected {} text region(s) but recognition failed on all of them",
+                    failed_regions
+                ),
+            )
+        }
+        Ok((text_boxes, _)) if text_boxes.is_empty() => {
+            JsonProtocolResponse::err(PROTOCOL_CODE_NO_TEXT, "No text detected")
+        }
+        Ok((text_boxes, _)) => JsonProtocolResponse::ok(text_boxes),
+        Err(e) => JsonProtocolResponse::err(PROTOCOL_CODE_ENGINE_ERROR, e.to_string()),
+    }
+}
+
+// visualize模式需要--output/--font才能画标注图，这项校验必须在init_engine
+// 加载模型之前做完，否则漏传参数也要先付模型加载的代价才能看到报错。
+// 三个入口（交互模式、单图模式、批处理模式）共用这一检查
+fn require_visualize_args(
+    mode: &OutputMode,
+    output_path: Option<&PathBuf>,
+    font_path: Option<&PathBuf>,
+) -> OcrResult<()> {
+    if *mode == OutputMode::Visualize {
+        if output_path.is_none() {
+            return Err(OcrError::InputError(
+                "--output is required in visualize mode".to_string(),
+            ));
+        }
+        if font_path.is_none() {
+            return Err(OcrError::InputError(
+                "--font is required in visualize mode".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn run_interactive_mode(
+    mode: OutputMode,
+    verbose: bool,
+    use_angle_cls: bool,
+    cls_threshold: f32,
+    output_path: Option<&PathBuf>,
+    font_path: Option<&PathBuf>,
+    det_config: DetectionConfig,
+    box_format: BoxFormat,
+) -> OcrResult<()> {
+    println!("PaddleOCR Interactive Mode Started");
+    println!("Enter image file paths to process (type 'exit' or 'quit' to exit):");
+
+    require_visualize_args(&mode, output_path, font_path)?;
+
+    init_engine(use_angle_cls, cls_threshold, det_config)?;
 
     loop {
         print!("> ");
@@ -173,7 +500,7 @@ fn run_interactive_mode(mode: OutputMode, verbose: bool) -> OcrResult<()> {
             .map(|ext| ext.to_lowercase())
             .unwrap_or_default();
         
-        if !["jpg", "jpeg", "png", "bmp", "tiff", "webp"].contains(&extension.as_str()) {
+        if !SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
             eprintln!("Warning: File does not appear to be an image: {}", input);
             print!("Do you want to continue? (y/N): ");
             io::stdout().flush().unwrap();
@@ -188,7 +515,7 @@ fn run_interactive_mode(mode: OutputMode, verbose: bool) -> OcrResult<()> {
         }
 
         // 处理OCR
-        match process_ocr_with_mode(&path, &mode, verbose) {
+        match process_ocr_with_mode(&path, &mode, verbose, output_path, font_path, &box_format, true) {
             Ok(_) => {
                 info!("OCR processing completed successfully.");
             },
@@ -202,21 +529,339 @@ fn run_interactive_mode(mode: OutputMode, verbose: bool) -> OcrResult<()> {
 }
 
 fn process_ocr(args: &Args, image_path: &PathBuf) -> OcrResult<()> {
-    // 初始化OCR引擎
-    info!(
-        "Initializing OCR engine from embedded PP-OCR{} models...",
-        models::VERSION
+    require_visualize_args(&args.mode, args.output.as_ref(), args.font.as_ref())?;
+
+    init_engine(
+        args.use_angle_cls,
+        args.cls_threshold,
+        DetectionConfig::from(args),
+    )?;
+
+    process_ocr_with_mode(
+        image_path,
+        &args.mode,
+        args.verbose,
+        args.output.as_ref(),
+        args.font.as_ref(),
+        &args.box_format,
+        false,
+    )
+}
+
+// 缓存条目以 "内容hash:模型版本:影响识别结果的配置指纹" 为键，
+// 这样切换模型版本或检测/识别相关的CLI参数都会自然使旧结果失效
+fn cache_key(file_bytes: &[u8], args: &Args) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(file_bytes);
+    let hash = hasher.finalize();
+    let config_fingerprint = format!(
+        "{:?}|cls={}|cls_thr={}|det_thresh={}|box_thresh={}|unclip={}|dilation={}|max_side={}",
+        args.box_format,
+        args.use_angle_cls,
+        args.cls_threshold,
+        args.det_db_thresh,
+        args.det_box_thresh,
+        args.det_unclip_ratio,
+        args.det_use_dilation,
+        args.det_max_side_len,
     );
-    OcrEngineManager::initialize_with_config_and_bytes(
-        DET_MODEL, REC_MODEL, KEYS_DATA, 12,    // rect_border_size
-        false, // merge_boxes
-        1,     // merge_threshold
+    format!(
+        "{}:{}:{}",
+        hex::encode(hash),
+        models::VERSION,
+        config_fingerprint
+    )
+}
+
+fn load_batch_cache(cache_file: &PathBuf) -> HashMap<String, Vec<TextBox>> {
+    match std::fs::read_to_string(cache_file) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save_batch_cache(
+    cache_file: &PathBuf,
+    cache: &HashMap<String, Vec<TextBox>>,
+) -> OcrResult<()> {
+    let json = serde_json::to_string_pretty(cache)
+        .map_err(|e| OcrError::OutputError(e.to_string()))?;
+    std::fs::write(cache_file, json)
+        .map_err(|e| OcrError::OutputError(format!("Failed to write cache {:?}: {}", cache_file, e)))
+}
+
+// 批处理目录内所有支持的图片，并通过内容哈希+模型版本的缓存跳过未变化的文件
+fn run_batch_mode(args: &Args, dir: &PathBuf) -> OcrResult<()> {
+    // 输入校验（glob模式、目录是否可读、visualize模式所需参数）应在加载模型之前完成，
+    // 否则一个写错的--glob也要先付模型加载的代价才能看到报错
+    let glob_pattern = args
+        .glob
+        .as_deref()
+        .map(glob::Pattern::new)
+        .transpose()
+        .map_err(|e| OcrError::InputError(format!("Invalid --glob pattern: {}", e)))?;
+
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| OcrError::InputError(format!("Failed to read directory {:?}: {}", dir, e)))?;
+
+    require_visualize_args(&args.mode, args.output.as_ref(), args.font.as_ref())?;
+
+    init_engine(
+        args.use_angle_cls,
+        args.cls_threshold,
+        DetectionConfig::from(args),
     )?;
 
-    process_ocr_with_mode(image_path, &args.mode, args.verbose)
+    let cache_dir = args.cache.clone().unwrap_or_else(|| dir.clone());
+    let cache_file = cache_dir.join(".ocr_cache.json");
+    let mut cache = load_batch_cache(&cache_file);
+
+    let mut new_results_since_flush: usize = 0;
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                error!("Failed to read directory entry: {}", e);
+                continue;
+            }
+        };
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let file_name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .unwrap_or_default();
+        if !SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+            continue;
+        }
+
+        if let Some(pattern) = &glob_pattern {
+            if !pattern.matches(file_name) {
+                continue;
+            }
+        }
+
+        // 单个文件失败不应丢弃本次运行已经识别出的其它结果，记录错误后跳过即可
+        let cache_len_before = cache.len();
+        match process_batch_file(&path, args, &mut cache, args.force) {
+            Ok((text_boxes, img)) => {
+                if let Err(e) = emit_batch_result(args, &path, &text_boxes, img) {
+                    error!("Failed to emit result for {:?}: {}", path, e);
+                }
+                // 只有真正新识别出结果（缓存未命中）时才计入落盘检查点，
+                // 避免对纯缓存命中的文件也触发重写
+                if cache.len() != cache_len_before {
+                    new_results_since_flush += 1;
+                    if new_results_since_flush >= BATCH_CACHE_CHECKPOINT_INTERVAL {
+                        if let Err(e) = save_batch_cache(&cache_file, &cache) {
+                            error!("Failed to checkpoint batch cache to {:?}: {}", cache_file, e);
+                        }
+                        new_results_since_flush = 0;
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to process {:?}: {}", path, e);
+            }
+        }
+    }
+
+    // 落盘最后一批未达到检查点间隔的新结果
+    if new_results_since_flush > 0 {
+        if let Err(e) = save_batch_cache(&cache_file, &cache) {
+            error!("Failed to save batch cache to {:?}: {}", cache_file, e);
+        }
+    }
+
+    Ok(())
+}
+
+// 处理批处理目录内的单个文件：命中缓存则直接返回，否则识别并写入缓存。
+// visualize模式无论是否命中缓存都需要解码后的图像用来绘制标注，因此随结果一并返回
+fn process_batch_file(
+    path: &PathBuf,
+    args: &Args,
+    cache: &mut HashMap<String, Vec<TextBox>>,
+    force: bool,
+) -> OcrResult<(Vec<TextBox>, Option<image::DynamicImage>)> {
+    let file_bytes = std::fs::read(path)
+        .map_err(|e| OcrError::InputError(format!("Failed to read {:?}: {}", path, e)))?;
+    let key = cache_key(&file_bytes, args);
+    let need_image = args.mode == OutputMode::Visualize;
+
+    if !force {
+        if let Some(cached) = cache.get(&key) {
+            info!("Using cached result for {:?}", path);
+            let img = if need_image {
+                Some(image::load_from_memory(&file_bytes)?)
+            } else {
+                None
+            };
+            return Ok((cached.clone(), img));
+        }
+    }
+
+    info!("Processing {:?}...", path);
+    let img = image::load_from_memory(&file_bytes)?;
+    let (text_boxes, _failed_regions) = recognize_text_boxes(&img, &args.box_format)?;
+    cache.insert(key, text_boxes.clone());
+    Ok((text_boxes, if need_image { Some(img) } else { None }))
+}
+
+// 按--mode将单个批处理文件的结果输出为JSON行、纯文本行或标注图像，
+// 与process_ocr_with_mode对单张图片的三种模式处理保持一致
+fn emit_batch_result(
+    args: &Args,
+    path: &PathBuf,
+    text_boxes: &[TextBox],
+    img: Option<image::DynamicImage>,
+) -> OcrResult<()> {
+    match args.mode {
+        OutputMode::Json => {
+            let json = serde_json::to_string(text_boxes)
+                .map_err(|e| OcrError::OutputError(e.to_string()))?;
+            println!("{}: {}", path.display(), json);
+        }
+        OutputMode::Text => {
+            for text_box in text_boxes {
+                println!("{}: {}", path.display(), text_box.text);
+            }
+        }
+        OutputMode::Visualize => {
+            // run_batch_mode已在处理任何文件之前校验过这两个参数的存在
+            let output_path = args
+                .output
+                .as_ref()
+                .expect("--output validated before batch processing started");
+            let font_path = args
+                .font
+                .as_ref()
+                .expect("--font validated before batch processing started");
+            let img = img.expect("image decoded for visualize mode in process_batch_file");
+            let output_path = derive_per_input_output_path(output_path, path);
+
+            render_annotated_image(&img, text_boxes, font_path, &output_path)?;
+            println!("Annotated image saved to {:?}", output_path);
+        }
+    }
+    Ok(())
 }
 
-fn process_ocr_with_mode(image_path: &PathBuf, mode: &OutputMode, verbose: bool) -> OcrResult<()> {
+// 检测 + 识别一张已加载的图像，返回带位置信息的文本框列表，以及识别失败的
+// 区域数；由JSON输出模式和逐行JSON协议模式共用。box_format决定position是
+// 轴对齐外接矩形还是检测器输出的原始四角多边形。调用方若只关心文本框可以
+// 忽略失败计数，但JSON协议模式需要区分"没检测到文字"和"检测到了但识别全部出错"
+fn recognize_text_boxes(
+    img: &image::DynamicImage,
+    box_format: &BoxFormat,
+) -> OcrResult<(Vec<TextBox>, usize)> {
+    // 获取文本区域图像，无论box_format如何都需要用它做识别
+    let text_images = OcrEngineManager::get_text_images(img)?;
+    info!("Found {} text regions", text_images.len());
+
+    if text_images.is_empty() {
+        info!("No text regions detected in the image.");
+        return Ok((Vec::new(), 0));
+    }
+
+    // rect格式下取轴对齐外接矩形；quad格式下直接取检测器原始的未矩形化多边形，
+    // 避免重复跑一遍检测器只为拿到quad模式用不上的rect
+    let text_rects = if *box_format == BoxFormat::Rect {
+        let rects = OcrEngineManager::get_text_rects(img)?;
+        if rects.len() != text_images.len() {
+            error!(
+                "Mismatch between text rectangles ({}) and text images ({})",
+                rects.len(),
+                text_images.len()
+            );
+            return Err(OcrError::EngineError(
+                "Inconsistent detection results".to_string(),
+            ));
+        }
+        Some(rects)
+    } else {
+        None
+    };
+
+    let text_polygons = if *box_format == BoxFormat::Quad {
+        let polygons = OcrEngineManager::get_text_polygons(img)?;
+        if polygons.len() != text_images.len() {
+            error!(
+                "Mismatch between text polygons ({}) and text images ({})",
+                polygons.len(),
+                text_images.len()
+            );
+            return Err(OcrError::EngineError(
+                "Inconsistent detection results".to_string(),
+            ));
+        }
+        Some(polygons)
+    } else {
+        None
+    };
+
+    let mut results = Vec::new();
+    let mut failed_regions = 0usize;
+
+    for (i, text_img) in text_images.iter().enumerate() {
+        info!("Processing text region {} of {}", i + 1, text_images.len());
+
+        // 检查子图像是否有效
+        if text_img.width() == 0 || text_img.height() == 0 {
+            error!("Invalid subimage with zero dimensions at index {}", i);
+            failed_regions += 1;
+            continue;
+        }
+
+        // 置信度为CTC贪心解码中存活时间步（折叠重复、丢弃blank后）上
+        // 最大softmax概率的均值，而非固定值
+        match OcrEngineManager::recognize_text_with_score(text_img.clone()) {
+            Ok((text, confidence)) => {
+                let position = match (&text_polygons, &text_rects) {
+                    (Some(polygons), _) => TextBoxPosition::Quad(polygons[i].clone()),
+                    (None, Some(rects)) => TextBoxPosition::Rect {
+                        left: rects[i].left(),
+                        top: rects[i].top(),
+                        width: rects[i].width(),
+                        height: rects[i].height(),
+                    },
+                    (None, None) => unreachable!("box_format selects exactly one of rects/polygons"),
+                };
+                results.push(TextBox {
+                    text,
+                    confidence,
+                    position,
+                });
+            }
+            Err(e) => {
+                error!("Failed to recognize text in region {}: {}", i, e);
+                failed_regions += 1;
+            }
+        }
+    }
+
+    Ok((results, failed_regions))
+}
+
+fn process_ocr_with_mode(
+    image_path: &PathBuf,
+    mode: &OutputMode,
+    verbose: bool,
+    output_path: Option<&PathBuf>,
+    font_path: Option<&PathBuf>,
+    box_format: &BoxFormat,
+    per_input_output: bool,
+) -> OcrResult<()> {
     // 加载图像
     info!("Loading image from {:?}...", image_path);
     let img = match image::open(image_path) {
@@ -235,61 +880,7 @@ fn process_ocr_with_mode(image_path: &PathBuf, mode: &OutputMode, verbose: bool)
         OutputMode::Json => {
             info!("Processing in JSON mode...");
 
-            // 获取文本区域矩形框
-            let text_rects = OcrEngineManager::get_text_rects(&img)?;
-            info!("Found {} text regions", text_rects.len());
-
-            if text_rects.is_empty() {
-                info!("No text regions detected in the image.");
-                println!("[]");
-                return Ok(());
-            }
-
-            // 获取文本区域图像
-            let text_images = OcrEngineManager::get_text_images(&img)?;
-            info!("Successfully extracted {} text images", text_images.len());
-
-            // 确保文本区域和图像数量一致
-            if text_rects.len() != text_images.len() {
-                error!(
-                    "Mismatch between text rectangles ({}) and text images ({})",
-                    text_rects.len(),
-                    text_images.len()
-                );
-                return Err(OcrError::EngineError(
-                    "Inconsistent detection results".to_string(),
-                ));
-            }
-
-            let mut results = Vec::new();
-
-            for (i, (rect, text_img)) in text_rects.iter().zip(text_images.iter()).enumerate() {
-                info!("Processing text region {} of {}", i + 1, text_rects.len());
-
-                // 检查子图像是否有效
-                if text_img.width() == 0 || text_img.height() == 0 {
-                    error!("Invalid subimage with zero dimensions at index {}", i);
-                    continue;
-                }
-
-                match OcrEngineManager::recognize_text(text_img.clone()) {
-                    Ok(text) => {
-                        results.push(TextBox {
-                            text,
-                            confidence: 1.0, // 使用引擎管理器无法获取置信度，设为默认值
-                            position: TextBoxPosition {
-                                left: rect.left(),
-                                top: rect.top(),
-                                width: rect.width(),
-                                height: rect.height(),
-                            },
-                        });
-                    }
-                    Err(e) => {
-                        error!("Failed to recognize text in region {}: {}", i, e);
-                    }
-                }
-            }
+            let (results, _failed_regions) = recognize_text_boxes(&img, box_format)?;
 
             // 输出JSON结果
             let json = serde_json::to_string_pretty(&results)
@@ -307,10 +898,123 @@ fn process_ocr_with_mode(image_path: &PathBuf, mode: &OutputMode, verbose: bool)
                 println!("{}", text);
             }
         }
+
+        OutputMode::Visualize => {
+            info!("Processing in visualize mode...");
+
+            let (results, _failed_regions) = recognize_text_boxes(&img, box_format)?;
+            // 调用方(process_ocr/run_interactive_mode)已通过require_visualize_args
+            // 在加载模型之前校验过这两个参数的存在
+            let output_path = output_path.expect("--output validated before visualize processing started");
+            let font_path = font_path.expect("--font validated before visualize processing started");
+
+            // 交互模式下可能连续处理多张图片，若都写同一个--output会互相覆盖，
+            // 因此按输入文件名派生各自的输出路径
+            let output_path = if per_input_output {
+                derive_per_input_output_path(output_path, image_path)
+            } else {
+                output_path.clone()
+            };
+
+            render_annotated_image(&img, &results, font_path, &output_path)?;
+            println!("Annotated image saved to {:?}", output_path);
+        }
     }
 
     Ok(())
 }
 
+// 由--output和当前输入图片路径派生一个该图片专属的输出路径，
+// 形如 <output的目录>/<output文件名主干>_<输入文件名（含扩展名）>.<output扩展名>。
+// 输入文件名必须带上自己的扩展名，否则同一目录下扩展名不同但文件名主干相同的
+// 两个输入（如page01.png和page01.jpg，批处理目录里常见）会派生出同一个输出路径，
+// 导致后一张图静默覆盖前一张的标注结果
+fn derive_per_input_output_path(output_path: &PathBuf, image_path: &PathBuf) -> PathBuf {
+    let input_file_name = image_path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("image");
+    let output_stem = output_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    let extension = output_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("png");
+    let file_name = format!("{}_{}.{}", output_stem, input_file_name, extension);
+
+    match output_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join(file_name),
+        _ => PathBuf::from(file_name),
+    }
+}
+
+// 在图像上绘制检测框与识别文本并保存，文本常见为中文等非ASCII字符，
+// 因此使用TrueType字体栅格化而非位图ASCII字体
+fn render_annotated_image(
+    img: &image::DynamicImage,
+    results: &[TextBox],
+    font_path: &PathBuf,
+    output_path: &PathBuf,
+) -> OcrResult<()> {
+    let font_data = std::fs::read(font_path)
+        .map_err(|e| OcrError::InputError(format!("Failed to read font {:?}: {}", font_path, e)))?;
+    let font = FontArc::try_from_vec(font_data)
+        .map_err(|e| OcrError::InputError(format!("Invalid font file {:?}: {}", font_path, e)))?;
+    let scale = PxScale::from(24.0);
+
+    let mut canvas = img.to_rgba8();
+    let box_color = image::Rgba([255, 0, 0, 255]);
+    let text_color = image::Rgba([0, 200, 0, 255]);
+
+    for result in results {
+        let (label_left, label_top) = match &result.position {
+            TextBoxPosition::Rect {
+                left,
+                top,
+                width,
+                height,
+            } => {
+                let rect = Rect::at(*left, *top).of_size((*width).max(1), (*height).max(1));
+                draw_hollow_rect_mut(&mut canvas, rect, box_color);
+                (*left, *top)
+            }
+            TextBoxPosition::Quad(points) => {
+                for i in 0..points.len() {
+                    let [x1, y1] = points[i];
+                    let [x2, y2] = points[(i + 1) % points.len()];
+                    imageproc::drawing::draw_line_segment_mut(
+                        &mut canvas,
+                        (x1, y1),
+                        (x2, y2),
+                        box_color,
+                    );
+                }
+                let min_x = points.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min);
+                let min_y = points.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min);
+                (min_x as i32, min_y as i32)
+            }
+        };
+
+        let label_y = (label_top - 20).max(0);
+        draw_text_mut(
+            &mut canvas,
+            text_color,
+            label_left,
+            label_y,
+            scale,
+            &font,
+            &result.text,
+        );
+    }
+
+    canvas
+        .save(output_path)
+        .map_err(|e| OcrError::OutputError(format!("Failed to save annotated image: {}", e)))?;
+
+    Ok(())
+}
+
 
 